@@ -4,10 +4,12 @@
 #![allow(unknown_lints)]
 #![allow(unexpected_cfgs)]
 
-use std::net::{ToSocketAddrs, UdpSocket};
-use std::sync::{Arc, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::net::{Ipv4Addr, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{bail, Result};
 use esp_idf_hal::rmt::VariableLengthSignal;
@@ -17,17 +19,30 @@ use esp_idf_hal::{
     rmt::{config::TransmitConfig, PinState, Pulse, TxRmtDriver},
 };
 
+use embedded_svc::ipv4::{
+    ClientConfiguration as IpClientConfiguration, ClientSettings, Configuration as IpConfiguration,
+    Mask, Subnet,
+};
+use embedded_svc::mqtt::client::{EventPayload, QoS};
 use embedded_svc::wifi::AuthMethod;
 use embedded_svc::wifi::{ClientConfiguration, Configuration};
+use esp_idf_svc::mqtt::client::{EspMqttClient, EspMqttEvent, MqttClientConfiguration};
+use esp_idf_svc::netif::{EspNetif, NetifConfiguration, NetifStack};
+use esp_idf_svc::sntp::{EspSntp, SyncStatus};
 use esp_idf_svc::{
     eventloop::EspSystemEventLoop,
     nvs::EspDefaultNvsPartition,
-    wifi::{BlockingWifi, EspWifi},
+    wifi::{BlockingWifi, EspWifi, WifiDriver},
 };
-use log::{info, warn};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
 
 const SSID: &str = env!("WIFI_SSID");
 const PASSWORD: &str = env!("WIFI_PASS");
+const MQTT_BROKER_URL: &str = env!("MQTT_BROKER_URL");
+
+const MQTT_TOPIC_SET: &str = "leds/stripe/set";
+const MQTT_TOPIC_STATE: &str = "leds/stripe/state";
 
 fn main() -> Result<(), anyhow::Error> {
     esp_idf_svc::sys::link_patches();
@@ -68,7 +83,8 @@ pub fn run_main() -> Result<()> {
     onboard_led_state.write().unwrap().push(Rgb::new(8, 0, 0));
 
     // RGB Stripe pin
-    let mut tx_stripe = TxRmtDriver::new(peripherals.rmt.channel1, peripherals.pins.gpio9, &config)?;
+    let mut tx_stripe =
+        TxRmtDriver::new(peripherals.rmt.channel1, peripherals.pins.gpio9, &config)?;
 
     let timings_ws2812b = [400, 800, 850, 450];
     let rgb_stripe_state = Arc::new(RwLock::new(Vec::with_capacity(50)));
@@ -81,23 +97,40 @@ pub fn run_main() -> Result<()> {
             .push(Rgb::from_hsv(150, 100, 13)?);
     }
 
+    // The onboard status LED is tiny and already dim; gamma correction would
+    // just make it harder to see, so it bypasses the stripe's output pipeline.
+    let onboard_output_config = LedOutputConfig::new(None, 255);
+    let stripe_output_config = LedOutputConfig::new(Some(DEFAULT_GAMMA), 255);
+
     send_led_signal(
         &onboard_led_state.read().unwrap(),
         &mut tx_onboard,
         &timings_ws2812,
+        &onboard_output_config,
     )?;
 
-    let mut wifi = BlockingWifi::wrap(
-        EspWifi::new(peripherals.modem, sys_loop.clone(), Some(nvs))?,
-        sys_loop,
+    let esp_wifi = EspWifi::wrap_all(
+        WifiDriver::new(peripherals.modem, sys_loop.clone(), Some(nvs))?,
+        EspNetif::new_with_conf(&sta_netif_configuration(static_ip_from_env()))?,
+        EspNetif::new(NetifStack::Ap)?,
     )?;
+    let mut wifi = BlockingWifi::wrap(esp_wifi, sys_loop)?;
     connect_wifi(&mut wifi)?;
 
+    // Needed so the sACN server can join the per-universe multicast groups on
+    // the right interface; grabbed before `wifi` is forgotten below.
+    let station_addr = wifi.wifi().sta_netif().get_ip_info()?.ip;
+
+    // Kept alive for the lifetime of `run_main` so the system clock keeps
+    // getting updated in the background.
+    let _sntp = setup_sntp()?;
+
     onboard_led_state.write().unwrap()[0] = Rgb::new(8, 0, 4);
     send_led_signal(
         &onboard_led_state.read().unwrap(),
         &mut tx_onboard,
         &timings_ws2812,
+        &onboard_output_config,
     )?;
 
     core::mem::forget(wifi);
@@ -106,18 +139,49 @@ pub fn run_main() -> Result<()> {
         &rgb_stripe_state.read().unwrap(),
         &mut tx_stripe,
         &timings_ws2812b,
+        &stripe_output_config,
     )?;
 
+    // Shared with the MQTT control task below, so both paths push to the
+    // same RMT channel instead of fighting over ownership of `tx_stripe`.
+    let tx_stripe = Arc::new(Mutex::new(tx_stripe));
+
     let onboard_led_clone = onboard_led_state.clone();
     let rgb_stripe_clone = rgb_stripe_state.clone();
+    let tx_stripe_clone = tx_stripe.clone();
+
+    // Only universe 1 is configured, filling the first 170 LEDs; each further
+    // universe listed here would fill the next 170, and so on.
+    let universe_mapping = contiguous_universe_mapping(&[1]);
 
     let _server = create_udp_server(
         onboard_led_clone,
         rgb_stripe_clone,
         tx_onboard,
-        tx_stripe,
+        tx_stripe_clone,
         timings_ws2812,
         timings_ws2812b,
+        universe_mapping,
+        station_addr,
+        onboard_output_config,
+        stripe_output_config.clone(),
+    );
+
+    let rgb_stripe_clone = rgb_stripe_state.clone();
+    let tx_stripe_clone = tx_stripe.clone();
+    let _mqtt_client = create_mqtt_client(
+        rgb_stripe_clone,
+        tx_stripe_clone,
+        timings_ws2812b,
+        stripe_output_config.clone(),
+    )?;
+
+    spawn_scheduler_thread(
+        rgb_stripe_state,
+        tx_stripe,
+        timings_ws2812b,
+        Schedule::default(),
+        stripe_output_config,
     );
 
     loop {
@@ -125,29 +189,275 @@ pub fn run_main() -> Result<()> {
     }
 }
 
+/// Root layer "ACN Packet Identifier" that every valid E1.31 (sACN) packet must carry.
+const ACN_PACKET_IDENTIFIER: [u8; 12] = *b"ASC-E1.17\0\0\0";
+
+/// Tracks the per-universe state needed to follow the E1.31 sequencing and
+/// priority-arbitration rules across packets.
+struct UniverseState {
+    last_sequence: u8,
+    active_priority: u8,
+    last_seen: Instant,
+}
+
+/// A source is considered gone once no packet has been seen from it for this long,
+/// at which point a lower-priority source is allowed to take over the universe.
+const SOURCE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Returns `true` if `sequence` is older than (or a duplicate of) `last_sequence`,
+/// per the E1.31 sequence-number handling rule: packets are compared with a signed
+/// 8-bit difference, and a difference of 20 or more in the "past" direction is
+/// assumed to be a legitimate wraparound rather than an out-of-order packet.
+fn is_sequence_out_of_order(sequence: u8, last_sequence: u8) -> bool {
+    let diff = sequence.wrapping_sub(last_sequence) as i8;
+    diff <= 0 && diff > -20
+}
+
+/// Parses the root, framing and DMP layers of an E1.31 data packet.
+///
+/// Returns the universe, sequence number, priority and the DMX channel
+/// payload (up to 512 channels, as many as the packet's property value count
+/// declares, with the leading START Code already stripped) on success.
+fn parse_sacn_packet(buf: &[u8]) -> Option<(u16, u8, u8, &[u8])> {
+    if buf.len() < 126 {
+        warn!(
+            "Received packet too small to be a valid sACN data packet: {} bytes",
+            buf.len()
+        );
+        return None;
+    }
+
+    if buf[4..16] != ACN_PACKET_IDENTIFIER {
+        warn!("Received packet with unrecognized ACN Packet Identifier, ignoring");
+        return None;
+    }
+
+    let priority = buf[108];
+    let sequence = buf[111];
+    let universe = u16::from_be_bytes(buf[113..=114].try_into().unwrap());
+    let property_value_count = u16::from_be_bytes(buf[123..=124].try_into().unwrap());
+
+    // Property value 1 is always the START Code, so a spec-compliant packet
+    // never has a count of 0; reject it here rather than underflowing the
+    // `dmx_data` slice bounds below.
+    if property_value_count < 1 {
+        warn!(
+            "Received packet with invalid property value count: {}",
+            property_value_count
+        );
+        return None;
+    }
+
+    if buf.len() < 125 + property_value_count as usize {
+        warn!(
+            "Received packet with insufficient size for property values: {}",
+            buf.len()
+        );
+        return None;
+    }
+
+    // Byte 125 is the DMX START Code (0x00 for regular dimmer data); channel
+    // data starts right after it.
+    let start_code = buf[125];
+    if start_code != 0x00 {
+        warn!(
+            "Ignoring packet with non-zero START Code: {:#04x}",
+            start_code
+        );
+        return None;
+    }
+
+    let dmx_data = &buf[126..(125 + property_value_count as usize)];
+
+    Some((universe, sequence, priority, dmx_data))
+}
+
+/// Computes the standard E1.31 multicast group address `239.255.{hi}.{lo}`
+/// for a universe, where `hi`/`lo` are the big-endian bytes of the universe
+/// number.
+fn sacn_multicast_group(universe: u16) -> Ipv4Addr {
+    let [hi, lo] = universe.to_be_bytes();
+    Ipv4Addr::new(239, 255, hi, lo)
+}
+
+/// Maps a single sACN universe onto the LED index at which its 170 pixels
+/// (512 channels / 3 bytes per pixel) start in the shared stripe buffer.
+#[derive(Clone, Copy)]
+struct UniverseMapping {
+    universe: u16,
+    start_led: usize,
+}
+
+/// Channel budget of a DMX512 universe expressed in whole LEDs.
+const LEDS_PER_UNIVERSE: usize = 170;
+
+/// Builds a contiguous mapping where `universes[0]` fills LEDs `0..170`,
+/// `universes[1]` fills `170..340`, and so on. Callers who need gaps or a
+/// different order can build a `Vec<UniverseMapping>` by hand instead.
+fn contiguous_universe_mapping(universes: &[u16]) -> Vec<UniverseMapping> {
+    universes
+        .iter()
+        .enumerate()
+        .map(|(i, &universe)| UniverseMapping {
+            universe,
+            start_led: i * LEDS_PER_UNIVERSE,
+        })
+        .collect()
+}
+
+/// How long to wait for the remaining configured universes of a frame before
+/// pushing whatever has arrived so far to the strip.
+const FRAME_ASSEMBLY_TIMEOUT: Duration = Duration::from_millis(30);
+
+/// Shadow state for the non-blocking sACN -> RMT push: the last frame
+/// actually transmitted (to skip identical refreshes), the instant the
+/// current transmission is expected to be done (to avoid starting a new one
+/// while the strip is still clocking the previous one out), and the signal
+/// buffer that transmission is still reading from.
+struct StripeTxState {
+    last_sent: Option<Vec<Rgb>>,
+    busy_until: Instant,
+    /// Kept alive until the next push (by which point `busy_until` guarantees
+    /// the RMT driver is done reading from it) because `start` returns before
+    /// the hardware/ISR has finished streaming the signal out of this buffer.
+    pending_signal: Option<VariableLengthSignal>,
+}
+
+impl StripeTxState {
+    fn new() -> Self {
+        Self {
+            last_sent: None,
+            busy_until: Instant::now(),
+            pending_signal: None,
+        }
+    }
+}
+
+/// Worst-case wall-clock time to clock `led_count` WS2812 pixels out over
+/// RMT, used by [`push_stripe_frame`] to tell whether the previous
+/// non-blocking transmission has finished.
+fn frame_duration(led_count: usize, timings: &[u64; 4]) -> Duration {
+    let bit_period_ns = (timings[0] + timings[1]).max(timings[2] + timings[3]);
+    Duration::from_nanos(bit_period_ns * 24 * led_count as u64)
+}
+
+/// Pushes `rgb_stripe_state` to `tx_stripe` unless it's identical to the last
+/// frame sent, using the non-blocking transmit path so the sACN receive loop
+/// is never stalled behind a full strip clock-out. If the previous
+/// transmission is still expected to be in flight this frame is dropped
+/// rather than queued, since a fresher one will follow shortly anyway.
+///
+/// Returns whether the caller can consider the frame handled: `true` if it
+/// was transmitted or was already identical to what's on the strip, `false`
+/// if it was dropped because the channel is still busy — in which case the
+/// caller must keep its dirty-tracking state so this (now stale but still
+/// freshest) frame gets retried instead of silently lost.
+fn push_stripe_frame(
+    rgb_stripe_state_lock: &Arc<RwLock<Vec<Rgb>>>,
+    tx_stripe: &Arc<Mutex<TxRmtDriver>>,
+    timings: &[u64; 4],
+    output_config: &LedOutputConfig,
+    state: &mut StripeTxState,
+) -> Result<bool> {
+    let current = rgb_stripe_state_lock.read().unwrap().clone();
+    if state.last_sent.as_ref() == Some(&current) {
+        return Ok(true);
+    }
+
+    let now = Instant::now();
+    if now < state.busy_until {
+        warn!("Dropping sACN frame, stripe RMT channel is still clocking out the previous one");
+        return Ok(false);
+    }
+
+    let signal = send_led_signal_nonblocking(
+        &current,
+        &mut tx_stripe.lock().unwrap(),
+        timings,
+        output_config,
+    )?;
+    state.busy_until = now + frame_duration(current.len(), timings);
+    state.last_sent = Some(current);
+    // `signal` must outlive the in-flight transmission `start` just kicked
+    // off; stash it here instead of letting it drop at the end of this call.
+    state.pending_signal = Some(signal);
+    Ok(true)
+}
+
 fn create_udp_server(
     onboard_led_state_lock: Arc<RwLock<Vec<Rgb>>>,
     rgb_stripe_state_lock: Arc<RwLock<Vec<Rgb>>>,
     mut tx_onboard: TxRmtDriver,
-    mut tx_stripe: TxRmtDriver,
+    tx_stripe: Arc<Mutex<TxRmtDriver>>,
     timings_ws2812: [u64; 4],
     timings_ws2812b: [u64; 4],
+    universe_mapping: Vec<UniverseMapping>,
+    station_addr: Ipv4Addr,
+    onboard_output_config: LedOutputConfig,
+    stripe_output_config: LedOutputConfig,
 ) -> Result<(), anyhow::Error> {
     let addr = "0.0.0.0:5568".to_socket_addrs()?.next().unwrap();
     let udp_socket = UdpSocket::bind(addr)?;
+    udp_socket.set_read_timeout(Some(FRAME_ASSEMBLY_TIMEOUT))?;
 
     info!("Created UDP server on {}", addr);
 
+    for mapping in &universe_mapping {
+        let group = sacn_multicast_group(mapping.universe);
+        udp_socket.join_multicast_v4(&group, &station_addr)?;
+        info!(
+            "Joined sACN multicast group {} for universe {}",
+            group, mapping.universe
+        );
+    }
+
     onboard_led_state_lock.write().unwrap()[0] = Rgb::new(0, 0, 8);
     send_led_signal(
         &onboard_led_state_lock.read().unwrap(),
         &mut tx_onboard,
         &timings_ws2812,
+        &onboard_output_config,
     )?;
 
+    let start_led_by_universe: HashMap<u16, usize> = universe_mapping
+        .iter()
+        .map(|m| (m.universe, m.start_led))
+        .collect();
+
+    let mut universes: HashMap<u16, UniverseState> = HashMap::new();
+    let mut universes_received: HashSet<u16> = HashSet::new();
+    let mut frame_dirty = false;
+    let mut frame_started = Instant::now();
+    let mut stripe_tx_state = StripeTxState::new();
+
     loop {
         let mut buf = [0u8; 638];
-        let (size, addr) = udp_socket.recv_from(&mut buf)?;
+        let received = match udp_socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                if frame_dirty {
+                    let handled = push_stripe_frame(
+                        &rgb_stripe_state_lock,
+                        &tx_stripe,
+                        &timings_ws2812b,
+                        &stripe_output_config,
+                        &mut stripe_tx_state,
+                    )?;
+                    if handled {
+                        universes_received.clear();
+                        frame_dirty = false;
+                    }
+                }
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let (size, addr) = received;
         info!("Received {} bytes from {}", size, addr);
 
         if !(125..=638).contains(&size) {
@@ -155,52 +465,472 @@ fn create_udp_server(
             continue;
         }
 
-        let universe = u16::from_be_bytes(buf[113..=114].try_into().unwrap());
-
-        let property_value_count = u16::from_be_bytes(buf[123..=124].try_into().unwrap());
+        let Some((universe, sequence, priority, dmx_data)) = parse_sacn_packet(&buf[..size]) else {
+            continue;
+        };
 
-        if size < 125 + property_value_count as usize {
+        let Some(&start_led) = start_led_by_universe.get(&universe) else {
             warn!(
-                "Received packet with insufficient size for property values: {}",
-                size
+                "Received data for unconfigured universe {}, ignoring",
+                universe
             );
             continue;
+        };
+
+        let now = Instant::now();
+        match universes.get_mut(&universe) {
+            Some(state) => {
+                let source_timed_out = now.duration_since(state.last_seen) > SOURCE_TIMEOUT;
+                if !source_timed_out && priority < state.active_priority {
+                    info!(
+                        "Ignoring universe {} packet with lower priority {} (active priority {})",
+                        universe, priority, state.active_priority
+                    );
+                    continue;
+                }
+                // A new source taking over the universe (because the old one timed
+                // out or a higher-priority one showed up) has its own, unrelated
+                // sequence counter, so the out-of-order check only applies while
+                // we're still tracking the same source's stream.
+                let is_takeover = source_timed_out || priority > state.active_priority;
+                if !is_takeover && is_sequence_out_of_order(sequence, state.last_sequence) {
+                    warn!(
+                        "Dropping out-of-order packet for universe {} (sequence {}, last {})",
+                        universe, sequence, state.last_sequence
+                    );
+                    continue;
+                }
+                state.last_sequence = sequence;
+                state.active_priority = priority;
+                state.last_seen = now;
+            }
+            None => {
+                universes.insert(
+                    universe,
+                    UniverseState {
+                        last_sequence: sequence,
+                        active_priority: priority,
+                        last_seen: now,
+                    },
+                );
+            }
         }
-        let property_values = &buf[125..(125 + property_value_count as usize)];
 
         {
             let mut rgb_stripe_state = rgb_stripe_state_lock.write().unwrap();
             info!(
-                "updating rgb leds based on universe {} from {}",
-                universe, addr
+                "updating rgb leds based on universe {} from {} (starting at led {})",
+                universe, addr, start_led
             );
 
-            for (i, chunk) in property_values.chunks(3).enumerate() {
-                if i >= rgb_stripe_state.len() {
-                    info!(
-                        "got data for more than {} leds ({} values)",
-                        i,
-                        property_value_count - 1
-                    );
+            for (i, chunk) in dmx_data.chunks_exact(3).enumerate() {
+                let led = start_led + i;
+                if led >= rgb_stripe_state.len() {
+                    info!("got data for more than {} leds", led);
                     break;
                 }
-                rgb_stripe_state[i] =
+                rgb_stripe_state[led] =
                     Rgb::from_slice(chunk.try_into().expect("slice with incorrect length"));
             }
         }
-        info!("updating rgb stripe color");
+        if !frame_dirty {
+            frame_started = now;
+        }
+        frame_dirty = true;
+        universes_received.insert(universe);
+
+        if universes_received.len() >= start_led_by_universe.len()
+            || now.duration_since(frame_started) >= FRAME_ASSEMBLY_TIMEOUT
+        {
+            info!("updating rgb stripe color");
+
+            let handled = push_stripe_frame(
+                &rgb_stripe_state_lock,
+                &tx_stripe,
+                &timings_ws2812b,
+                &stripe_output_config,
+                &mut stripe_tx_state,
+            )?;
+
+            info!("updated rgb stripe color");
+            if handled {
+                universes_received.clear();
+                frame_dirty = false;
+            }
+        }
+    }
+}
 
-        send_led_signal(
-            &rgb_stripe_state_lock.read().unwrap(),
-            &mut tx_stripe,
-            &timings_ws2812b,
-        )?;
+/// JSON control payload accepted on [`MQTT_TOPIC_SET`]; the resulting state is
+/// published back on [`MQTT_TOPIC_STATE`] as a [`LedState`]. All fields are
+/// optional; a received command only touches the fields that are present.
+/// When `effect` is present, `color` and `segments` are ignored, and when
+/// `color` is present, `segments` is ignored.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct LedCommand {
+    /// Sets every LED on the stripe to a single RGB color.
+    color: Option<[u8; 3]>,
+    /// Sets each LED individually, in order.
+    segments: Option<Vec<[u8; 3]>>,
+    /// Sets the master brightness (0-255) applied to every frame, including
+    /// ones driven by sACN.
+    brightness: Option<u8>,
+    /// Starts a named built-in effect ("rainbow" or "breathe"), or stops the
+    /// active effect if the name isn't recognized.
+    effect: Option<String>,
+}
+
+/// Built-in animations the `effect` field of [`LedCommand`] can select.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Effect {
+    Rainbow,
+    Breathe,
+}
 
-        info!("updated rgb stripe color");
+impl Effect {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rainbow" => Some(Effect::Rainbow),
+            "breathe" => Some(Effect::Breathe),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Effect::Rainbow => "rainbow",
+            Effect::Breathe => "breathe",
+        }
     }
 }
 
-fn send_led_signal(rgb: &[Rgb], tx: &mut TxRmtDriver, timings: &[u64; 4]) -> Result<()> {
+/// Snapshot of the stripe state published on [`MQTT_TOPIC_STATE`] after every
+/// accepted [`LedCommand`].
+#[derive(Debug, Serialize)]
+struct LedState {
+    segments: Vec<[u8; 3]>,
+    brightness: u8,
+    effect: Option<&'static str>,
+}
+
+/// How often the background effect thread renders a new frame.
+const EFFECT_FRAME_INTERVAL_MS: u32 = 40;
+
+/// Stack size for the MQTT connection-event thread. It parses the incoming
+/// JSON command, builds and transmits the RMT signal, and forwards the
+/// resulting state to the publisher thread, so it needs more headroom than a
+/// trivial default.
+const MQTT_CONNECTION_STACK_SIZE: usize = 16 * 1024;
+
+/// Sets up the MQTT control channel: subscribes to [`MQTT_TOPIC_SET`] for
+/// incoming commands, spawns a dedicated thread that publishes the resulting
+/// state on [`MQTT_TOPIC_STATE`], and starts the background thread that
+/// drives the built-in effects.
+fn create_mqtt_client(
+    rgb_stripe_state_lock: Arc<RwLock<Vec<Rgb>>>,
+    tx_stripe: Arc<Mutex<TxRmtDriver>>,
+    timings_ws2812b: [u64; 4],
+    output_config: LedOutputConfig,
+) -> Result<Arc<Mutex<EspMqttClient<'static>>>> {
+    let mqtt_config = MqttClientConfiguration::default();
+    let (client, mut connection) = EspMqttClient::new(MQTT_BROKER_URL, &mqtt_config)?;
+    let client = Arc::new(Mutex::new(client));
+
+    info!("Connecting to MQTT broker at {}", MQTT_BROKER_URL);
+
+    let active_effect: Arc<Mutex<Option<Effect>>> = Arc::new(Mutex::new(None));
+
+    // esp-idf-svc blocks the MQTT task on each event until it's dropped, and
+    // the connection thread below is still holding it while handling a
+    // command, so publishing inline from there is the documented deadlock
+    // path. State snapshots are handed off over a channel to this dedicated
+    // thread instead, which publishes them with no event in flight.
+    let (state_tx, state_rx) = mpsc::channel::<LedState>();
+    let client_for_publish = client.clone();
+    thread::spawn(move || {
+        for state in state_rx {
+            publish_led_state(&client_for_publish, &state);
+        }
+    });
+
+    let rgb_stripe_state_for_conn = rgb_stripe_state_lock.clone();
+    let tx_stripe_for_conn = tx_stripe.clone();
+    let active_effect_for_conn = active_effect.clone();
+    let output_config_for_conn = output_config.clone();
+
+    thread::Builder::new()
+        .stack_size(MQTT_CONNECTION_STACK_SIZE)
+        .spawn(move || {
+            while let Ok(event) = connection.next() {
+                handle_mqtt_event(
+                    &event,
+                    &state_tx,
+                    &rgb_stripe_state_for_conn,
+                    &tx_stripe_for_conn,
+                    &timings_ws2812b,
+                    &active_effect_for_conn,
+                    &output_config_for_conn,
+                );
+            }
+        })?;
+
+    client
+        .lock()
+        .unwrap()
+        .subscribe(MQTT_TOPIC_SET, QoS::AtLeastOnce)?;
+    info!("Subscribed to {}", MQTT_TOPIC_SET);
+
+    spawn_effect_thread(
+        rgb_stripe_state_lock,
+        tx_stripe,
+        timings_ws2812b,
+        active_effect,
+        output_config,
+    );
+
+    Ok(client)
+}
+
+fn handle_mqtt_event(
+    event: &EspMqttEvent,
+    state_tx: &mpsc::Sender<LedState>,
+    rgb_stripe_state_lock: &Arc<RwLock<Vec<Rgb>>>,
+    tx_stripe: &Arc<Mutex<TxRmtDriver>>,
+    timings_ws2812b: &[u64; 4],
+    active_effect: &Arc<Mutex<Option<Effect>>>,
+    output_config: &LedOutputConfig,
+) {
+    let EventPayload::Received {
+        topic: Some(topic),
+        data,
+        ..
+    } = event.payload()
+    else {
+        return;
+    };
+
+    if topic != MQTT_TOPIC_SET {
+        return;
+    }
+
+    let command: LedCommand = match serde_json::from_slice(data) {
+        Ok(command) => command,
+        Err(e) => {
+            warn!("Ignoring malformed MQTT command: {}", e);
+            return;
+        }
+    };
+
+    apply_led_command(
+        command,
+        state_tx,
+        rgb_stripe_state_lock,
+        tx_stripe,
+        timings_ws2812b,
+        active_effect,
+        output_config,
+    );
+}
+
+/// Applies a [`LedCommand`] to the shared stripe state, pushes the new
+/// colors to the strip unless the command carries an `effect` (which then
+/// takes the place of any `color`/`segments` in it), and hands the resulting [`LedState`] snapshot off to the publisher thread
+/// (see [`create_mqtt_client`]) to send on [`MQTT_TOPIC_STATE`].
+fn apply_led_command(
+    command: LedCommand,
+    state_tx: &mpsc::Sender<LedState>,
+    rgb_stripe_state_lock: &Arc<RwLock<Vec<Rgb>>>,
+    tx_stripe: &Arc<Mutex<TxRmtDriver>>,
+    timings_ws2812b: &[u64; 4],
+    active_effect: &Arc<Mutex<Option<Effect>>>,
+    output_config: &LedOutputConfig,
+) {
+    if let Some(brightness) = command.brightness {
+        *output_config.brightness.write().unwrap() = brightness;
+        info!("Set master brightness to {}", brightness);
+    }
+
+    let mut handled_effect = false;
+    if let Some(name) = &command.effect {
+        let effect = Effect::from_name(name);
+        *active_effect.lock().unwrap() = effect;
+        match effect {
+            Some(_) => info!("Starting built-in effect '{}'", name),
+            None => info!("Stopping built-in effect"),
+        }
+        handled_effect = true;
+    }
+
+    if !handled_effect {
+        let updated = if let Some([r, g, b]) = command.color {
+            let mut state = rgb_stripe_state_lock.write().unwrap();
+            for led in state.iter_mut() {
+                *led = Rgb::new(r, g, b);
+            }
+            true
+        } else if let Some(segments) = &command.segments {
+            let mut state = rgb_stripe_state_lock.write().unwrap();
+            for (led, [r, g, b]) in state.iter_mut().zip(segments.iter()) {
+                *led = Rgb::new(*r, *g, *b);
+            }
+            true
+        } else {
+            false
+        };
+
+        if updated {
+            *active_effect.lock().unwrap() = None;
+
+            if let Err(e) = send_led_signal(
+                &rgb_stripe_state_lock.read().unwrap(),
+                &mut tx_stripe.lock().unwrap(),
+                timings_ws2812b,
+                output_config,
+            ) {
+                error!("Failed to update rgb stripe after MQTT command: {}", e);
+            }
+        }
+    }
+
+    let state = LedState {
+        segments: rgb_stripe_state_lock
+            .read()
+            .unwrap()
+            .iter()
+            .map(|led| [led.r, led.g, led.b])
+            .collect(),
+        brightness: *output_config.brightness.read().unwrap(),
+        effect: active_effect.lock().unwrap().map(|effect| effect.name()),
+    };
+    if state_tx.send(state).is_err() {
+        warn!("MQTT state publisher thread is gone, dropping led state update");
+    }
+}
+
+/// Serializes `state` and publishes it on [`MQTT_TOPIC_STATE`]. Runs on the
+/// dedicated publisher thread spawned by [`create_mqtt_client`], never on
+/// the `connection.next()` event thread: esp-idf-svc blocks the MQTT task on
+/// the in-flight event until it's dropped, so publishing from inside the
+/// handler that's still holding it would deadlock.
+fn publish_led_state(client: &Arc<Mutex<EspMqttClient<'static>>>, state: &LedState) {
+    let payload = match serde_json::to_vec(state) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!("Failed to serialize led state for MQTT publish: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) =
+        client
+            .lock()
+            .unwrap()
+            .publish(MQTT_TOPIC_STATE, QoS::AtLeastOnce, false, &payload)
+    {
+        error!("Failed to publish led state on {}: {}", MQTT_TOPIC_STATE, e);
+    }
+}
+
+/// Drives the `rainbow` and `breathe` built-in effects in the background
+/// while one of them is selected via MQTT.
+fn spawn_effect_thread(
+    rgb_stripe_state_lock: Arc<RwLock<Vec<Rgb>>>,
+    tx_stripe: Arc<Mutex<TxRmtDriver>>,
+    timings_ws2812b: [u64; 4],
+    active_effect: Arc<Mutex<Option<Effect>>>,
+    output_config: LedOutputConfig,
+) {
+    thread::spawn(move || {
+        let mut step: u32 = 0;
+        loop {
+            let effect = *active_effect.lock().unwrap();
+            if let Some(effect) = effect {
+                let len = rgb_stripe_state_lock.read().unwrap().len().max(1) as u32;
+                {
+                    let mut state = rgb_stripe_state_lock.write().unwrap();
+                    for (i, led) in state.iter_mut().enumerate() {
+                        *led = match effect {
+                            Effect::Rainbow => {
+                                let hue = (step + (i as u32 * 360 / len)) % 360;
+                                Rgb::from_hsv(hue, 100, 100).unwrap_or(*led)
+                            }
+                            Effect::Breathe => {
+                                let phase = (step % 100) as f64 / 100.0 * std::f64::consts::PI;
+                                let value = (phase.sin().abs() * 100.0) as u32;
+                                Rgb::from_hsv(0, 0, value).unwrap_or(*led)
+                            }
+                        };
+                    }
+                }
+                if let Err(e) = send_led_signal(
+                    &rgb_stripe_state_lock.read().unwrap(),
+                    &mut tx_stripe.lock().unwrap(),
+                    &timings_ws2812b,
+                    &output_config,
+                ) {
+                    error!("Failed to render effect frame: {}", e);
+                }
+                step = step.wrapping_add(1);
+            }
+            FreeRtos::delay_ms(EFFECT_FRAME_INTERVAL_MS);
+        }
+    });
+}
+
+/// Default gamma used to build the correction lookup table. WS2812 LEDs are
+/// perceptually very nonlinear at low levels, so raw 8-bit values produce
+/// banding and harsh fades; gamma correction spreads the low end back out.
+const DEFAULT_GAMMA: f64 = 2.2;
+
+/// Computes a 256-entry `u8` gamma-correction lookup table for `gamma`.
+fn build_gamma_table(gamma: f64) -> [u8; 256] {
+    let mut table = [0u8; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        *entry = (255.0 * (i as f64 / 255.0).powf(gamma)).round() as u8;
+    }
+    table
+}
+
+/// Gamma correction and master brightness applied to every `Rgb` right
+/// before it's packed into the wire format, without touching how colors are
+/// sourced (sACN, MQTT, effects, scheduler all go through the same path).
+#[derive(Clone)]
+struct LedOutputConfig {
+    /// `None` bypasses gamma correction entirely.
+    gamma_table: Option<[u8; 256]>,
+    /// Master brightness, 0-255, applied after gamma correction.
+    brightness: Arc<RwLock<u8>>,
+}
+
+impl LedOutputConfig {
+    /// `gamma`: `Some(value)` to gamma-correct with the given exponent (the
+    /// 256-entry table is computed once up front), `None` to bypass it.
+    fn new(gamma: Option<f64>, brightness: u8) -> Self {
+        Self {
+            gamma_table: gamma.map(build_gamma_table),
+            brightness: Arc::new(RwLock::new(brightness)),
+        }
+    }
+
+    fn apply(&self, color: Rgb) -> Rgb {
+        let brightness = *self.brightness.read().unwrap() as u32;
+        let channel = |v: u8| -> u8 {
+            let v = match &self.gamma_table {
+                Some(table) => table[v as usize],
+                None => v,
+            };
+            ((v as u32 * brightness) / 255) as u8
+        };
+        Rgb::new(channel(color.r), channel(color.g), channel(color.b))
+    }
+}
+
+fn build_led_signal(
+    rgb: &[Rgb],
+    tx: &TxRmtDriver,
+    timings: &[u64; 4],
+    output_config: &LedOutputConfig,
+) -> Result<VariableLengthSignal> {
     let ticks_hz = tx.counter_clock()?;
     let (t0h, t0l, t1h, t1l) = (
         Pulse::new_with_duration(ticks_hz, PinState::High, &Duration::from_nanos(timings[0]))?,
@@ -211,7 +941,7 @@ fn send_led_signal(rgb: &[Rgb], tx: &mut TxRmtDriver, timings: &[u64; 4]) -> Res
     let mut signal = VariableLengthSignal::new();
     for color in rgb {
         // Convert RGB to u32 color value
-        let color: u32 = color.into();
+        let color: u32 = (&output_config.apply(*color)).into();
         // Each color is 24 bits, so we need 24 pulses
         for i in (0..24).rev() {
             let p = 2_u32.pow(i);
@@ -221,10 +951,41 @@ fn send_led_signal(rgb: &[Rgb], tx: &mut TxRmtDriver, timings: &[u64; 4]) -> Res
         }
     }
 
+    Ok(signal)
+}
+
+fn send_led_signal(
+    rgb: &[Rgb],
+    tx: &mut TxRmtDriver,
+    timings: &[u64; 4],
+    output_config: &LedOutputConfig,
+) -> Result<()> {
+    let signal = build_led_signal(rgb, tx, timings, output_config)?;
     tx.start_blocking(&signal)?;
     Ok(())
 }
-#[derive(Copy, Clone)]
+
+/// Same as [`send_led_signal`] but queues the transmission with the
+/// non-blocking `start` instead of waiting for the RMT channel to finish
+/// clocking the bits out. Used on the sACN hot path so a busy strip never
+/// stalls the UDP receive loop behind it; see [`push_stripe_frame`] for the
+/// accompanying "is the previous frame still in flight" bookkeeping.
+///
+/// Returns the built `signal` rather than dropping it: the RMT driver keeps
+/// reading from its buffer via ISR well after `start` returns, so the caller
+/// must keep it alive for (at least) as long as the transmission is in
+/// flight instead of letting it go out of scope here.
+fn send_led_signal_nonblocking(
+    rgb: &[Rgb],
+    tx: &mut TxRmtDriver,
+    timings: &[u64; 4],
+    output_config: &LedOutputConfig,
+) -> Result<VariableLengthSignal> {
+    let signal = build_led_signal(rgb, tx, timings, output_config)?;
+    tx.start(&signal)?;
+    Ok(signal)
+}
+#[derive(Copy, Clone, PartialEq)]
 struct Rgb {
     r: u8,
     g: u8,
@@ -283,6 +1044,47 @@ impl From<&Rgb> for u32 {
     }
 }
 
+/// Reads the optional `STATIC_IP` / `STATIC_GATEWAY` / `STATIC_NETMASK`
+/// (a prefix length, e.g. `24`) build-time variables. Returns `None`
+/// (meaning DHCP) unless all three are set and parse successfully.
+fn static_ip_from_env() -> Option<(Ipv4Addr, Ipv4Addr, u8)> {
+    let ip = option_env!("STATIC_IP")?.parse().ok()?;
+    let gateway = option_env!("STATIC_GATEWAY")?.parse().ok()?;
+    let netmask_prefix_len = option_env!("STATIC_NETMASK")?.parse().ok()?;
+    Some((ip, gateway, netmask_prefix_len))
+}
+
+/// Builds the STA netif configuration: a fixed IP/gateway/netmask when
+/// [`static_ip_from_env`] returns one, or the default DHCP client otherwise.
+/// Lighting installations are often on isolated networks with no DHCP
+/// server, and a fixed address is also needed for reliable unicast sACN and
+/// MQTT broker reachability.
+fn sta_netif_configuration(static_ip: Option<(Ipv4Addr, Ipv4Addr, u8)>) -> NetifConfiguration {
+    let mut conf = NetifConfiguration::wifi_default_client();
+
+    match static_ip {
+        Some((ip, gateway, netmask_prefix_len)) => {
+            info!(
+                "Using static IP {} (gateway {}, /{})",
+                ip, gateway, netmask_prefix_len
+            );
+            conf.ip_configuration =
+                IpConfiguration::Client(IpClientConfiguration::Fixed(ClientSettings {
+                    ip,
+                    subnet: Subnet {
+                        gateway,
+                        mask: Mask(netmask_prefix_len),
+                    },
+                    dns: None,
+                    secondary_dns: None,
+                }));
+        }
+        None => info!("No static IP configured, falling back to DHCP"),
+    }
+
+    conf
+}
+
 fn connect_wifi(wifi: &mut BlockingWifi<EspWifi<'static>>) -> Result<()> {
     let wifi_configuration: Configuration = Configuration::Client(ClientConfiguration {
         ssid: SSID.try_into().unwrap(),
@@ -306,3 +1108,105 @@ fn connect_wifi(wifi: &mut BlockingWifi<EspWifi<'static>>) -> Result<()> {
 
     Ok(())
 }
+
+/// Starts the SNTP client and blocks until the system clock has completed
+/// its first sync, so later wall-clock reads (the scheduler) are meaningful
+/// right away instead of racing an unsynced clock.
+fn setup_sntp() -> Result<EspSntp<'static>> {
+    let sntp = EspSntp::new_default()?;
+
+    info!("Waiting for SNTP time sync...");
+    while sntp.get_sync_status() != SyncStatus::Completed {
+        FreeRtos::delay_ms(200);
+    }
+    info!("SNTP time synced");
+
+    Ok(sntp)
+}
+
+/// Configures the time-of-day behavior driven by [`spawn_scheduler_thread`].
+/// All hours are UTC, 0-23.
+struct Schedule {
+    /// Hour at which the stripe is switched off for the night.
+    off_hour: u32,
+    /// Hour at which the sunrise ramp starts (and the stripe turns back on).
+    sunrise_hour: u32,
+    /// How long the sunrise ramp takes to reach full brightness.
+    sunrise_duration: Duration,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            off_hour: 23,
+            sunrise_hour: 7,
+            sunrise_duration: Duration::from_secs(20 * 60),
+        }
+    }
+}
+
+/// How often the scheduler re-evaluates the current time of day.
+const SCHEDULER_POLL_INTERVAL_MS: u32 = 30_000;
+
+/// Returns how many seconds have passed since UTC midnight.
+fn seconds_since_midnight_utc() -> u32 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    (now.as_secs() % 86400) as u32
+}
+
+/// Whether `hour` falls in the `[off_hour, sunrise_hour)` window, accounting
+/// for the window wrapping past midnight.
+fn in_off_window(hour: u32, off_hour: u32, sunrise_hour: u32) -> bool {
+    if off_hour <= sunrise_hour {
+        (off_hour..sunrise_hour).contains(&hour)
+    } else {
+        hour >= off_hour || hour < sunrise_hour
+    }
+}
+
+/// Runs time-based behavior on the stripe so it's useful even with no sACN
+/// source or MQTT controller connected: switches the strip off overnight and
+/// ramps it back up at dawn using [`Rgb::from_hsv`].
+fn spawn_scheduler_thread(
+    rgb_stripe_state_lock: Arc<RwLock<Vec<Rgb>>>,
+    tx_stripe: Arc<Mutex<TxRmtDriver>>,
+    timings_ws2812b: [u64; 4],
+    schedule: Schedule,
+    output_config: LedOutputConfig,
+) {
+    thread::spawn(move || loop {
+        let seconds_of_day = seconds_since_midnight_utc();
+        let hour = seconds_of_day / 3600;
+
+        let frame = if hour == schedule.sunrise_hour {
+            let elapsed_secs = (seconds_of_day - schedule.sunrise_hour * 3600) as f64;
+            let progress = (elapsed_secs / schedule.sunrise_duration.as_secs_f64()).clamp(0.0, 1.0);
+            Rgb::from_hsv(30, 80, (progress * 100.0) as u32).ok()
+        } else if in_off_window(hour, schedule.off_hour, schedule.sunrise_hour) {
+            Some(Rgb::new(0, 0, 0))
+        } else {
+            None
+        };
+
+        if let Some(color) = frame {
+            {
+                let mut state = rgb_stripe_state_lock.write().unwrap();
+                for led in state.iter_mut() {
+                    *led = color;
+                }
+            }
+            if let Err(e) = send_led_signal(
+                &rgb_stripe_state_lock.read().unwrap(),
+                &mut tx_stripe.lock().unwrap(),
+                &timings_ws2812b,
+                &output_config,
+            ) {
+                error!("Failed to render scheduled frame: {}", e);
+            }
+        }
+
+        FreeRtos::delay_ms(SCHEDULER_POLL_INTERVAL_MS);
+    });
+}